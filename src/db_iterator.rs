@@ -0,0 +1,270 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ColumnFamily;
+use Error;
+use ReadOptions;
+use transaction::Transaction;
+
+use ffi;
+use libc::size_t;
+
+use std::marker::PhantomData;
+use std::slice;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+pub type KVBytes = (Box<[u8]>, Box<[u8]>);
+
+pub enum IteratorMode<'a> {
+    Start,
+    End,
+    From(&'a [u8], Direction),
+}
+
+/// A forward/backward-iterating cursor over a transaction's view of a column family (its own
+/// pending writes layered on top of the DB).
+///
+/// `'a` ties this iterator to the `Transaction` it reads through (and transitively to the DB
+/// underneath it), so the borrow checker rejects an iterator that outlives its transaction —
+/// the same use-after-free `Transaction`/`Snapshot` guard against one level up.
+pub struct DBIterator<'a> {
+    inner: *mut ffi::rocksdb_iterator_t,
+    direction: Direction,
+    just_seeked: bool,
+    marker: PhantomData<&'a ()>,
+}
+
+impl<'a> DBIterator<'a> {
+    pub fn new_txn(
+        txn: &'a Transaction,
+        r_opts: &ReadOptions,
+        mode: IteratorMode,
+    ) -> DBIterator<'a> {
+        let inner = unsafe { ffi::rocksdb_transaction_create_iterator(txn.inner, r_opts.inner) };
+        DBIterator::from_raw(inner, mode)
+    }
+
+    pub fn new_txn_cf(
+        txn: &'a Transaction,
+        cf: ColumnFamily,
+        r_opts: &ReadOptions,
+        mode: IteratorMode,
+    ) -> Result<DBIterator<'a>, Error> {
+        let inner = unsafe {
+            ffi::rocksdb_transaction_create_iterator_cf(txn.inner, r_opts.inner, cf.inner)
+        };
+        Ok(DBIterator::from_raw(inner, mode))
+    }
+
+    fn from_raw(inner: *mut ffi::rocksdb_iterator_t, mode: IteratorMode) -> DBIterator<'a> {
+        let mut iter = DBIterator {
+            inner,
+            direction: Direction::Forward,
+            just_seeked: false,
+            marker: PhantomData,
+        };
+        iter.seek(mode);
+        iter
+    }
+
+    pub fn valid(&self) -> bool {
+        unsafe { ffi::rocksdb_iter_valid(self.inner) != 0 }
+    }
+
+    pub fn seek(&mut self, mode: IteratorMode) {
+        match mode {
+            IteratorMode::Start => {
+                self.direction = Direction::Forward;
+                unsafe { ffi::rocksdb_iter_seek_to_first(self.inner) }
+            }
+            IteratorMode::End => {
+                self.direction = Direction::Reverse;
+                unsafe { ffi::rocksdb_iter_seek_to_last(self.inner) }
+            }
+            IteratorMode::From(key, dir) => {
+                self.direction = dir;
+                unsafe {
+                    match dir {
+                        Direction::Forward => ffi::rocksdb_iter_seek(
+                            self.inner,
+                            key.as_ptr() as *const _,
+                            key.len() as size_t,
+                        ),
+                        Direction::Reverse => ffi::rocksdb_iter_seek_for_prev(
+                            self.inner,
+                            key.as_ptr() as *const _,
+                            key.len() as size_t,
+                        ),
+                    }
+                }
+            }
+        }
+        self.just_seeked = true;
+    }
+}
+
+impl<'a> Iterator for DBIterator<'a> {
+    type Item = KVBytes;
+
+    fn next(&mut self) -> Option<KVBytes> {
+        if !self.valid() {
+            return None;
+        }
+
+        if self.just_seeked {
+            self.just_seeked = false;
+        } else {
+            unsafe {
+                match self.direction {
+                    Direction::Forward => ffi::rocksdb_iter_next(self.inner),
+                    Direction::Reverse => ffi::rocksdb_iter_prev(self.inner),
+                }
+            }
+        }
+
+        if !self.valid() {
+            return None;
+        }
+
+        unsafe {
+            let mut key_len: size_t = 0;
+            let key_ptr = ffi::rocksdb_iter_key(self.inner, &mut key_len) as *const u8;
+            let key = slice::from_raw_parts(key_ptr, key_len as usize)
+                .to_vec()
+                .into_boxed_slice();
+
+            let mut val_len: size_t = 0;
+            let val_ptr = ffi::rocksdb_iter_value(self.inner, &mut val_len) as *const u8;
+            let value = slice::from_raw_parts(val_ptr, val_len as usize)
+                .to_vec()
+                .into_boxed_slice();
+
+            Some((key, value))
+        }
+    }
+}
+
+impl<'a> Drop for DBIterator<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_destroy(self.inner);
+        }
+    }
+}
+
+unsafe impl<'a> Send for DBIterator<'a> {}
+
+/// A lower-level cursor over a transaction's view of a column family, exposing `key()`/`value()`
+/// without allocating a `(Box<[u8]>, Box<[u8]>)` pair per step.
+///
+/// Carries the same `'a` borrow of its parent `Transaction` as `DBIterator`.
+pub struct DBRawIterator<'a> {
+    inner: *mut ffi::rocksdb_iterator_t,
+    marker: PhantomData<&'a ()>,
+}
+
+impl<'a> DBRawIterator<'a> {
+    pub fn new_txn(txn: &'a Transaction, r_opts: &ReadOptions) -> DBRawIterator<'a> {
+        let inner = unsafe { ffi::rocksdb_transaction_create_iterator(txn.inner, r_opts.inner) };
+        DBRawIterator {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn new_txn_cf(
+        txn: &'a Transaction,
+        cf: ColumnFamily,
+        r_opts: &ReadOptions,
+    ) -> Result<DBRawIterator<'a>, Error> {
+        let inner = unsafe {
+            ffi::rocksdb_transaction_create_iterator_cf(txn.inner, r_opts.inner, cf.inner)
+        };
+        Ok(DBRawIterator {
+            inner,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn valid(&self) -> bool {
+        unsafe { ffi::rocksdb_iter_valid(self.inner) != 0 }
+    }
+
+    pub fn seek_to_first(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_seek_to_first(self.inner);
+        }
+    }
+
+    pub fn seek_to_last(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_seek_to_last(self.inner);
+        }
+    }
+
+    pub fn seek(&mut self, key: &[u8]) {
+        unsafe {
+            ffi::rocksdb_iter_seek(self.inner, key.as_ptr() as *const _, key.len() as size_t);
+        }
+    }
+
+    pub fn next(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_next(self.inner);
+        }
+    }
+
+    pub fn prev(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_prev(self.inner);
+        }
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        if !self.valid() {
+            return None;
+        }
+        unsafe {
+            let mut key_len: size_t = 0;
+            let key_ptr = ffi::rocksdb_iter_key(self.inner, &mut key_len) as *const u8;
+            Some(slice::from_raw_parts(key_ptr, key_len as usize))
+        }
+    }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        if !self.valid() {
+            return None;
+        }
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val_ptr = ffi::rocksdb_iter_value(self.inner, &mut val_len) as *const u8;
+            Some(slice::from_raw_parts(val_ptr, val_len as usize))
+        }
+    }
+}
+
+impl<'a> Drop for DBRawIterator<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_destroy(self.inner);
+        }
+    }
+}
+
+unsafe impl<'a> Send for DBRawIterator<'a> {}
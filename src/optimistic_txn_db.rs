@@ -16,6 +16,7 @@ use ColumnFamily;
 use DBIterator;
 use DBRawIterator;
 use DBVector;
+use Direction;
 use Error;
 use IteratorMode;
 use Options;
@@ -23,6 +24,7 @@ use ReadOptions;
 use WriteOptions;
 use db::Inner;
 use transaction::Transaction;
+use transaction_db::{TransactionDB, TransactionOptions};
 use utils;
 
 use std::collections::BTreeMap;
@@ -36,11 +38,30 @@ use libc::{c_uchar, c_char, size_t, c_int, c_void};
 unsafe impl Send for OptimisticTransactionDB {}
 unsafe impl Sync for OptimisticTransactionDB {}
 
-#[derive(Clone)]
+/// A column family to open alongside its own `Options`, rather than inheriting the database's
+/// default options for every family.
+pub struct ColumnFamilyDescriptor {
+    pub(crate) name: String,
+    pub(crate) options: Options,
+}
+
+impl ColumnFamilyDescriptor {
+    pub fn new<S: Into<String>>(name: S, options: Options) -> ColumnFamilyDescriptor {
+        ColumnFamilyDescriptor {
+            name: name.into(),
+            options,
+        }
+    }
+}
+
 pub struct OptimisticTransactionDB {
     pub inner: *mut ffi::rocksdb_optimistictransactiondb_t,
     base_db: *mut ffi::rocksdb_t,
     cfs: BTreeMap<String, ColumnFamily>,
+    // Keeps the per-column-family `Options` passed to `open_cf_descriptors` alive for as long as
+    // the database is open, since RocksDB holds on to the pointers (e.g. any merge operator or
+    // comparator they reference) rather than copying them at open time.
+    cf_opts: Vec<Options>,
 }
 
 impl OptimisticTransactionDB {
@@ -70,10 +91,27 @@ impl OptimisticTransactionDB {
             inner: db,
             base_db,
             cfs: BTreeMap::new(),
+            cf_opts: Vec::new(),
         })
     }
 
+    /// Opens the given column families with the database's default `Options`. To tune each
+    /// family individually (block cache, compression, merge operator, comparator, ...), use
+    /// `open_cf_descriptors` instead.
     pub fn open_cf<P: AsRef<Path>>(opts: &Options, path: P, cfs: &[&str]) -> Result<Self, Error> {
+        let descriptors = cfs
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()))
+            .collect::<Vec<_>>();
+        Self::open_cf_descriptors(opts, path, descriptors)
+    }
+
+    /// Opens the given column families, each with its own `Options`.
+    pub fn open_cf_descriptors<P: AsRef<Path>>(
+        opts: &Options,
+        path: P,
+        cfs: Vec<ColumnFamilyDescriptor>,
+    ) -> Result<Self, Error> {
         let path = path.as_ref();
         let cpath = utils::to_cpath(path)?;
         let db: *mut ffi::rocksdb_optimistictransactiondb_t;
@@ -86,79 +124,88 @@ impl OptimisticTransactionDB {
                     cpath.as_ptr() as *const _
                 ));
             }
-        } else {
-            let mut cfs_v = cfs.to_vec();
-            // Always open the default column family.
-            if !cfs_v.contains(&"default") {
-                cfs_v.push("default");
-            }
+            return if db.is_null() {
+                Err(Error::new("Could not initialize database.".to_owned()))
+            } else {
+                let base_db = unsafe { ffi::rocksdb_optimistictransactiondb_get_base_db(db) };
+                Ok(OptimisticTransactionDB {
+                    inner: db,
+                    base_db,
+                    cfs: cf_map,
+                    cf_opts: Vec::new(),
+                })
+            };
+        }
 
-            // We need to store our CStrings in an intermediate vector
-            // so that their pointers remain valid.
-            let c_cfs: Vec<CString> = cfs_v
-                .iter()
-                .map(|cf| CString::new(cf.as_bytes()).unwrap())
-                .collect();
+        let mut cfs = cfs;
+        // Always open the default column family.
+        if !cfs.iter().any(|d| d.name == "default") {
+            cfs.push(ColumnFamilyDescriptor::new("default", Options::default()));
+        }
 
-            let cfnames: Vec<_> = c_cfs.iter().map(|cf| cf.as_ptr()).collect();
+        // We need to store our CStrings in an intermediate vector
+        // so that their pointers remain valid.
+        let c_cfs: Vec<CString> = cfs
+            .iter()
+            .map(|d| CString::new(d.name.as_bytes()).unwrap())
+            .collect();
 
-            // These handles will be populated by DB.
-            let mut cfhandles: Vec<_> = cfs_v.iter().map(|_| ptr::null_mut()).collect();
+        let cfnames: Vec<_> = c_cfs.iter().map(|cf| cf.as_ptr()).collect();
 
-            // TODO(tyler) allow options to be passed in.
-            let cfopts: Vec<_> = cfs_v
-                .iter()
-                .map(|_| unsafe { ffi::rocksdb_options_create() as *const _ })
-                .collect();
+        // These handles will be populated by DB.
+        let mut cfhandles: Vec<_> = cfs.iter().map(|_| ptr::null_mut()).collect();
 
-            unsafe {
-                db = ffi_try!(ffi::rocksdb_optimistictransactiondb_open_column_families(
-                    opts.inner,
-                    cpath.as_ptr() as *const _,
-                    cfs_v.len() as c_int,
-                    cfnames.as_ptr() as *const _,
-                    cfopts.as_ptr(),
-                    cfhandles.as_mut_ptr()
-                ));
-            }
+        let cfopts: Vec<_> = cfs.iter().map(|d| d.options.inner as *const _).collect();
 
-            for handle in &cfhandles {
-                if handle.is_null() {
-                    return Err(Error::new(
-                        "Received null column family \
-                                           handle from DB."
-                            .to_owned(),
-                    ));
-                }
-            }
+        unsafe {
+            db = ffi_try!(ffi::rocksdb_optimistictransactiondb_open_column_families(
+                opts.inner,
+                cpath.as_ptr() as *const _,
+                cfs.len() as c_int,
+                cfnames.as_ptr() as *const _,
+                cfopts.as_ptr(),
+                cfhandles.as_mut_ptr()
+            ));
+        }
 
-            for (n, h) in cfs_v.iter().zip(cfhandles) {
-                cf_map.insert(n.to_string(), ColumnFamily { inner: h });
+        for handle in &cfhandles {
+            if handle.is_null() {
+                return Err(Error::new(
+                    "Received null column family \
+                                       handle from DB."
+                        .to_owned(),
+                ));
             }
         }
 
+        for (d, h) in cfs.iter().zip(&cfhandles) {
+            cf_map.insert(d.name.clone(), ColumnFamily { inner: *h });
+        }
+
         if db.is_null() {
             return Err(Error::new("Could not initialize database.".to_owned()));
         }
 
         let base_db = unsafe { ffi::rocksdb_optimistictransactiondb_get_base_db(db) };
+        let cf_opts = cfs.into_iter().map(|d| d.options).collect();
 
         Ok(OptimisticTransactionDB {
             inner: db,
             base_db,
             cfs: cf_map,
+            cf_opts,
         })
     }
 
-    pub fn transaction_begin(
-        &self,
+    pub fn transaction_begin<'a>(
+        &'a self,
         w_opts: &WriteOptions,
         txn_opts: &OptimisticTransactionOptions,
-    ) -> Transaction {
+    ) -> Transaction<'a> {
         Transaction::new_optimistic(self, w_opts, txn_opts)
     }
 
-    pub fn snapshot(&self) -> Snapshot {
+    pub fn snapshot<'a>(&'a self) -> Snapshot<'a> {
         Snapshot::new(self)
     }
 
@@ -252,13 +299,17 @@ impl Drop for OptimisticTransactionDB {
     }
 }
 
-pub struct Snapshot {
+/// A point-in-time read view taken from an `OptimisticTransactionDB`.
+///
+/// `'a` ties the snapshot to the database it was taken from, so it cannot outlive the `DB` that
+/// backs its underlying `rocksdb_snapshot_t` pointer.
+pub struct Snapshot<'a> {
     inner: *mut ffi::rocksdb_snapshot_t,
-    transaction: Transaction,
+    transaction: Transaction<'a>,
 }
 
-impl Snapshot {
-    pub fn new(db: &OptimisticTransactionDB) -> Snapshot {
+impl<'a> Snapshot<'a> {
+    pub fn new(db: &'a OptimisticTransactionDB) -> Snapshot<'a> {
         let w_opts = WriteOptions::default();
         let mut txn_opts = OptimisticTransactionOptions::default();
         txn_opts.set_snapshot(true);
@@ -270,29 +321,75 @@ impl Snapshot {
         }
     }
 
-    pub fn iterator(&self, mode: IteratorMode) -> DBIterator {
+    /// Takes a snapshot from a pessimistically-locking `TransactionDB`; see
+    /// `OptimisticTransactionDB::snapshot`/`Snapshot::new`.
+    pub fn new_pessimistic(db: &'a TransactionDB) -> Snapshot<'a> {
+        let w_opts = WriteOptions::default();
+        let mut txn_opts = TransactionOptions::default();
+        txn_opts.set_snapshot(true);
+        let transaction = db.transaction_begin(&w_opts, &txn_opts);
+        let snapshot = unsafe { ffi::rocksdb_transaction_get_snapshot(transaction.inner) };
+        Snapshot {
+            transaction,
+            inner: snapshot,
+        }
+    }
+
+    pub fn iterator<'b>(&'b self, mode: IteratorMode) -> DBIterator<'b> {
         let mut r_opts = ReadOptions::default();
         r_opts.set_snapshot(self);
         DBIterator::new_txn(&self.transaction, &r_opts, mode)
     }
 
-    pub fn iterator_cf(
-        &self,
+    pub fn iterator_cf<'b>(
+        &'b self,
         cf_handle: ColumnFamily,
         mode: IteratorMode,
-    ) -> Result<DBIterator, Error> {
+    ) -> Result<DBIterator<'b>, Error> {
         let mut r_opts = ReadOptions::default();
         r_opts.set_snapshot(self);
         DBIterator::new_txn_cf(&self.transaction, cf_handle, &r_opts, mode)
     }
 
-    pub fn raw_iterator(&self) -> DBRawIterator {
+    /// Scans all keys sharing `prefix` as of this snapshot.
+    ///
+    /// As with `Transaction::prefix_iterator`, this only bounds the scan to `prefix` when the
+    /// database was opened with an `Options::set_prefix_extractor` covering `prefix`'s length;
+    /// without one, RocksDB gives no guarantee the iterator stops at the prefix boundary.
+    pub fn prefix_iterator<'b>(&'b self, prefix: &[u8]) -> DBIterator<'b> {
+        let mut r_opts = ReadOptions::default();
+        r_opts.set_snapshot(self);
+        r_opts.set_prefix_same_as_start(true);
+        DBIterator::new_txn(
+            &self.transaction,
+            &r_opts,
+            IteratorMode::From(prefix, Direction::Forward),
+        )
+    }
+
+    pub fn prefix_iterator_cf<'b>(
+        &'b self,
+        cf_handle: ColumnFamily,
+        prefix: &[u8],
+    ) -> Result<DBIterator<'b>, Error> {
+        let mut r_opts = ReadOptions::default();
+        r_opts.set_snapshot(self);
+        r_opts.set_prefix_same_as_start(true);
+        DBIterator::new_txn_cf(
+            &self.transaction,
+            cf_handle,
+            &r_opts,
+            IteratorMode::From(prefix, Direction::Forward),
+        )
+    }
+
+    pub fn raw_iterator<'b>(&'b self) -> DBRawIterator<'b> {
         let mut r_opts = ReadOptions::default();
         r_opts.set_snapshot(self);
         DBRawIterator::new_txn(&self.transaction, &r_opts)
     }
 
-    pub fn raw_iterator_cf(&self, cf_handle: ColumnFamily) -> Result<DBRawIterator, Error> {
+    pub fn raw_iterator_cf<'b>(&'b self, cf_handle: ColumnFamily) -> Result<DBRawIterator<'b>, Error> {
         let mut r_opts = ReadOptions::default();
         r_opts.set_snapshot(self);
         DBRawIterator::new_txn_cf(&self.transaction, cf_handle, &r_opts)
@@ -311,7 +408,7 @@ impl Snapshot {
     }
 }
 
-impl Drop for Snapshot {
+impl<'a> Drop for Snapshot<'a> {
     fn drop(&mut self) {
         unsafe {
             ffi::rocksdb_free(self.inner as *mut c_void);
@@ -319,7 +416,7 @@ impl Drop for Snapshot {
     }
 }
 
-impl Inner for Snapshot {
+impl<'a> Inner for Snapshot<'a> {
     fn get_inner(&self) -> *const ffi::rocksdb_snapshot_t {
         self.inner
     }
@@ -0,0 +1,491 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ColumnFamily;
+use DBIterator;
+use DBRawIterator;
+use DBVector;
+use Direction;
+use Error;
+use IteratorMode;
+use ReadOptions;
+use WriteOptions;
+use optimistic_txn_db::{OptimisticTransactionDB, OptimisticTransactionOptions};
+use transaction_db::{TransactionDB, TransactionOptions};
+
+use ffi;
+use libc::{c_char, c_uchar, c_void, size_t};
+
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::ptr;
+
+/// A transaction against either an `OptimisticTransactionDB` or a `TransactionDB`.
+///
+/// `'a` ties this handle to the database that created it (via `PhantomData`), so the borrow
+/// checker rejects a `Transaction` that outlives its parent `DB` — dropping the database while a
+/// transaction (or an iterator/snapshot derived from one) is still alive would otherwise be a
+/// use-after-free on the raw `rocksdb_transaction_t` pointer.
+pub struct Transaction<'a> {
+    pub inner: *mut ffi::rocksdb_transaction_t,
+    marker: PhantomData<&'a ()>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new_optimistic(
+        db: &'a OptimisticTransactionDB,
+        w_opts: &WriteOptions,
+        txn_opts: &OptimisticTransactionOptions,
+    ) -> Transaction<'a> {
+        let inner = unsafe {
+            ffi::rocksdb_optimistictransaction_begin(
+                db.inner,
+                w_opts.inner,
+                txn_opts.inner,
+                ::std::ptr::null_mut(),
+            )
+        };
+        Transaction {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn new_pessimistic(
+        db: &'a TransactionDB,
+        w_opts: &WriteOptions,
+        txn_opts: &TransactionOptions,
+    ) -> Transaction<'a> {
+        let inner = unsafe {
+            ffi::rocksdb_transaction_begin(
+                db.inner,
+                w_opts.inner,
+                txn_opts.inner,
+                ::std::ptr::null_mut(),
+            )
+        };
+        Transaction {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    /// Wraps a `rocksdb_transaction_t` handle recovered from
+    /// `TransactionDB::get_prepared_transactions`, rather than one begun fresh.
+    pub(crate) fn from_raw(inner: *mut ffi::rocksdb_transaction_t) -> Transaction<'a> {
+        Transaction {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<DBVector>, Error> {
+        let r_opts = ReadOptions::default();
+        self.get_opt(key, &r_opts)
+    }
+
+    pub fn get_opt(&self, key: &[u8], r_opts: &ReadOptions) -> Result<Option<DBVector>, Error> {
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val = ffi_try!(ffi::rocksdb_transaction_get(
+                self.inner,
+                r_opts.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                &mut val_len
+            )) as *mut u8;
+            if val.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(DBVector::from_c(val, val_len)))
+            }
+        }
+    }
+
+    pub fn get_cf(&self, key: &[u8], cf: ColumnFamily) -> Result<Option<DBVector>, Error> {
+        let r_opts = ReadOptions::default();
+        self.get_cf_opt(key, cf, &r_opts)
+    }
+
+    pub fn get_cf_opt(
+        &self,
+        key: &[u8],
+        cf: ColumnFamily,
+        r_opts: &ReadOptions,
+    ) -> Result<Option<DBVector>, Error> {
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val = ffi_try!(ffi::rocksdb_transaction_get_cf(
+                self.inner,
+                r_opts.inner,
+                cf.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                &mut val_len
+            )) as *mut u8;
+            if val.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(DBVector::from_c(val, val_len)))
+            }
+        }
+    }
+
+    /// Reads `key` and acquires a lock on it for the lifetime of the transaction. Under
+    /// `TransactionDB` a concurrent writer touching the same key waits for the lock and fails once
+    /// its lock timeout expires; under `OptimisticTransactionDB` this transaction's own `commit()`
+    /// fails if another writer changed the key after the read. Pass `exclusive = false` to take a
+    /// shared read lock instead of an exclusive write lock; only `TransactionDB` honors the shared
+    /// case, as `OptimisticTransactionDB` has no real locking underneath.
+    pub fn get_for_update(
+        &self,
+        key: &[u8],
+        exclusive: bool,
+    ) -> Result<Option<DBVector>, Error> {
+        let r_opts = ReadOptions::default();
+        self.get_for_update_opt(key, exclusive, &r_opts)
+    }
+
+    pub fn get_for_update_opt(
+        &self,
+        key: &[u8],
+        exclusive: bool,
+        r_opts: &ReadOptions,
+    ) -> Result<Option<DBVector>, Error> {
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val = ffi_try!(ffi::rocksdb_transaction_get_for_update(
+                self.inner,
+                r_opts.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                &mut val_len,
+                exclusive as c_uchar
+            )) as *mut u8;
+            if val.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(DBVector::from_c(val, val_len)))
+            }
+        }
+    }
+
+    pub fn get_for_update_cf(
+        &self,
+        key: &[u8],
+        cf: ColumnFamily,
+        exclusive: bool,
+    ) -> Result<Option<DBVector>, Error> {
+        let r_opts = ReadOptions::default();
+        self.get_for_update_cf_opt(key, cf, exclusive, &r_opts)
+    }
+
+    pub fn get_for_update_cf_opt(
+        &self,
+        key: &[u8],
+        cf: ColumnFamily,
+        exclusive: bool,
+        r_opts: &ReadOptions,
+    ) -> Result<Option<DBVector>, Error> {
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val = ffi_try!(ffi::rocksdb_transaction_get_for_update_cf(
+                self.inner,
+                r_opts.inner,
+                cf.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                &mut val_len,
+                exclusive as c_uchar
+            )) as *mut u8;
+            if val.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(DBVector::from_c(val, val_len)))
+            }
+        }
+    }
+
+    /// Looks up several keys in a single FFI round trip, reflecting this transaction's own
+    /// pending writes the same way `get` does.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Vec<Result<Option<DBVector>, Error>> {
+        let r_opts = ReadOptions::default();
+        self.multi_get_opt(keys, &r_opts)
+    }
+
+    pub fn multi_get_opt(
+        &self,
+        keys: &[&[u8]],
+        r_opts: &ReadOptions,
+    ) -> Vec<Result<Option<DBVector>, Error>> {
+        unsafe { self.multi_get_raw(None, keys, r_opts) }
+    }
+
+    pub fn multi_get_cf(
+        &self,
+        cf: ColumnFamily,
+        keys: &[&[u8]],
+    ) -> Vec<Result<Option<DBVector>, Error>> {
+        let r_opts = ReadOptions::default();
+        self.multi_get_cf_opt(cf, keys, &r_opts)
+    }
+
+    pub fn multi_get_cf_opt(
+        &self,
+        cf: ColumnFamily,
+        keys: &[&[u8]],
+        r_opts: &ReadOptions,
+    ) -> Vec<Result<Option<DBVector>, Error>> {
+        unsafe { self.multi_get_raw(Some(cf), keys, r_opts) }
+    }
+
+    unsafe fn multi_get_raw(
+        &self,
+        cf: Option<ColumnFamily>,
+        keys: &[&[u8]],
+        r_opts: &ReadOptions,
+    ) -> Vec<Result<Option<DBVector>, Error>> {
+        let num_keys = keys.len();
+        let keys_list: Vec<_> = keys.iter().map(|k| k.as_ptr() as *const c_char).collect();
+        let keys_list_sizes: Vec<_> = keys.iter().map(|k| k.len() as size_t).collect();
+        let mut values_list: Vec<*mut c_char> = vec![ptr::null_mut(); num_keys];
+        let mut values_list_sizes: Vec<size_t> = vec![0; num_keys];
+        let mut errs: Vec<*mut c_char> = vec![ptr::null_mut(); num_keys];
+
+        match cf {
+            Some(cf) => {
+                let cfs = vec![cf.inner; num_keys];
+                ffi::rocksdb_transaction_multi_get_cf(
+                    self.inner,
+                    r_opts.inner,
+                    cfs.as_ptr(),
+                    num_keys as size_t,
+                    keys_list.as_ptr(),
+                    keys_list_sizes.as_ptr(),
+                    values_list.as_mut_ptr(),
+                    values_list_sizes.as_mut_ptr(),
+                    errs.as_mut_ptr(),
+                );
+            }
+            None => {
+                ffi::rocksdb_transaction_multi_get(
+                    self.inner,
+                    r_opts.inner,
+                    num_keys as size_t,
+                    keys_list.as_ptr(),
+                    keys_list_sizes.as_ptr(),
+                    values_list.as_mut_ptr(),
+                    values_list_sizes.as_mut_ptr(),
+                    errs.as_mut_ptr(),
+                );
+            }
+        }
+
+        (0..num_keys)
+            .map(|i| {
+                if !errs[i].is_null() {
+                    let message = CStr::from_ptr(errs[i]).to_string_lossy().into_owned();
+                    ffi::rocksdb_free(errs[i] as *mut c_void);
+                    Err(Error::new(message))
+                } else if values_list[i].is_null() {
+                    Ok(None)
+                } else {
+                    Ok(Some(DBVector::from_c(
+                        values_list[i] as *mut u8,
+                        values_list_sizes[i],
+                    )))
+                }
+            })
+            .collect()
+    }
+
+    /// Scans all keys sharing `prefix`, reflecting this transaction's pending writes.
+    ///
+    /// `set_prefix_same_as_start` only bounds the scan to `prefix` relative to whatever
+    /// `prefix_extractor` the (column family's) `Options` were opened with; without one
+    /// configured, RocksDB makes no guarantee the iterator stops at the prefix boundary. Open the
+    /// database (or column family, via `ColumnFamilyDescriptor`) with
+    /// `Options::set_prefix_extractor` set to a `SliceTransform` covering `prefix`'s length
+    /// before relying on this to be prefix-bounded.
+    pub fn prefix_iterator<'b>(&'b self, prefix: &[u8]) -> DBIterator<'b> {
+        let mut r_opts = ReadOptions::default();
+        r_opts.set_prefix_same_as_start(true);
+        DBIterator::new_txn(
+            self,
+            &r_opts,
+            IteratorMode::From(prefix, Direction::Forward),
+        )
+    }
+
+    pub fn prefix_iterator_cf<'b>(
+        &'b self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+    ) -> Result<DBIterator<'b>, Error> {
+        let mut r_opts = ReadOptions::default();
+        r_opts.set_prefix_same_as_start(true);
+        DBIterator::new_txn_cf(
+            self,
+            cf,
+            &r_opts,
+            IteratorMode::From(prefix, Direction::Forward),
+        )
+    }
+
+    pub fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_put(
+                self.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                value.as_ptr() as *const c_char,
+                value.len() as size_t
+            ));
+            Ok(())
+        }
+    }
+
+    pub fn put_cf(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_put_cf(
+                self.inner,
+                cf.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                value.as_ptr() as *const c_char,
+                value.len() as size_t
+            ));
+            Ok(())
+        }
+    }
+
+    pub fn delete(&self, key: &[u8]) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_delete(
+                self.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t
+            ));
+            Ok(())
+        }
+    }
+
+    pub fn delete_cf(&self, cf: ColumnFamily, key: &[u8]) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_delete_cf(
+                self.inner,
+                cf.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t
+            ));
+            Ok(())
+        }
+    }
+
+    pub fn iterator<'b>(&'b self) -> DBIterator<'b> {
+        let r_opts = ReadOptions::default();
+        DBIterator::new_txn(self, &r_opts, IteratorMode::Start)
+    }
+
+    pub fn iterator_cf<'b>(&'b self, cf: ColumnFamily) -> Result<DBIterator<'b>, Error> {
+        let r_opts = ReadOptions::default();
+        DBIterator::new_txn_cf(self, cf, &r_opts, IteratorMode::Start)
+    }
+
+    pub fn raw_iterator<'b>(&'b self) -> DBRawIterator<'b> {
+        let r_opts = ReadOptions::default();
+        DBRawIterator::new_txn(self, &r_opts)
+    }
+
+    pub fn raw_iterator_cf<'b>(&'b self, cf: ColumnFamily) -> Result<DBRawIterator<'b>, Error> {
+        let r_opts = ReadOptions::default();
+        DBRawIterator::new_txn_cf(self, cf, &r_opts)
+    }
+
+    pub fn savepoint(&self) {
+        unsafe {
+            ffi::rocksdb_transaction_set_savepoint(self.inner);
+        }
+    }
+
+    pub fn rollback_to_savepoint(&self) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_rollback_to_savepoint(self.inner));
+            Ok(())
+        }
+    }
+
+    pub fn commit(&self) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_commit(self.inner));
+            Ok(())
+        }
+    }
+
+    pub fn rollback(&self) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_rollback(self.inner));
+            Ok(())
+        }
+    }
+
+    /// Names this transaction so it can be recovered by name via
+    /// `TransactionDB::get_prepared_transactions` if the process crashes after `prepare()` but
+    /// before `commit()`/`rollback()`. Must be called at most once, before any writes.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_set_name(
+                self.inner,
+                name.as_ptr() as *const c_char,
+                name.len() as size_t
+            ));
+            Ok(())
+        }
+    }
+
+    pub fn get_name(&self) -> Option<String> {
+        unsafe {
+            let mut name_len: size_t = 0;
+            let name = ffi::rocksdb_transaction_get_name(self.inner, &mut name_len);
+            if name.is_null() {
+                None
+            } else {
+                let slice = ::std::slice::from_raw_parts(name as *const u8, name_len as usize);
+                Some(String::from_utf8_lossy(slice).into_owned())
+            }
+        }
+    }
+
+    /// Persists this transaction's write set to the WAL in a prepared state without releasing
+    /// its locks, the first phase of a two-phase commit. A crash after `prepare()` leaves the
+    /// transaction recoverable by name via `TransactionDB::get_prepared_transactions` on reopen,
+    /// so a coordinator can later resolve it with `commit()` or `rollback()`.
+    pub fn prepare(&self) -> Result<(), Error> {
+        unsafe {
+            ffi_try!(ffi::rocksdb_transaction_prepare(self.inner));
+            Ok(())
+        }
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_transaction_destroy(self.inner);
+        }
+    }
+}
+
+unsafe impl<'a> Send for Transaction<'a> {}
+unsafe impl<'a> Sync for Transaction<'a> {}
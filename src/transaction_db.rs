@@ -0,0 +1,373 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ColumnFamily;
+use DBVector;
+use Error;
+use Options;
+use ReadOptions;
+use WriteOptions;
+use optimistic_txn_db::{ColumnFamilyDescriptor, Snapshot};
+use transaction::Transaction;
+use utils;
+
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+
+use ffi;
+use libc::{c_char, c_int, c_uchar, size_t};
+
+unsafe impl Send for TransactionDB {}
+unsafe impl Sync for TransactionDB {}
+
+/// A pessimistically-locking transactional database, backed by `rocksdb_transactiondb_*`.
+///
+/// Unlike `OptimisticTransactionDB`, which only detects write-write conflicts at commit time,
+/// `TransactionDB` acquires real row locks as keys are written (or read via `get_for_update`), so
+/// a conflicting writer blocks or fails immediately instead of racing to commit first.
+pub struct TransactionDB {
+    pub inner: *mut ffi::rocksdb_transactiondb_t,
+    cfs: BTreeMap<String, ColumnFamily>,
+    // Keeps the per-column-family `Options` passed to `open_cf_descriptors` alive for as long as
+    // the database is open; see `OptimisticTransactionDB::cf_opts`.
+    cf_opts: Vec<Options>,
+}
+
+impl TransactionDB {
+    pub fn open_default<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        let txn_db_opts = TransactionDBOptions::default();
+        Self::open(&options, &txn_db_opts, path)
+    }
+
+    pub fn open<P: AsRef<Path>>(
+        opts: &Options,
+        txn_db_opts: &TransactionDBOptions,
+        path: P,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let cpath = utils::to_cpath(path)?;
+        let db: *mut ffi::rocksdb_transactiondb_t = unsafe {
+            ffi_try!(ffi::rocksdb_transactiondb_open(
+                opts.inner,
+                txn_db_opts.inner,
+                cpath.as_ptr() as *const _
+            ))
+        };
+
+        if db.is_null() {
+            return Err(Error::new("Could not initialize database.".to_owned()));
+        }
+
+        Ok(TransactionDB {
+            inner: db,
+            cfs: BTreeMap::new(),
+            cf_opts: Vec::new(),
+        })
+    }
+
+    /// Opens the given column families with the database's default `Options`. To tune each
+    /// family individually (block cache, compression, merge operator, comparator, ...), use
+    /// `open_cf_descriptors` instead.
+    pub fn open_cf<P: AsRef<Path>>(
+        opts: &Options,
+        txn_db_opts: &TransactionDBOptions,
+        path: P,
+        cfs: &[&str],
+    ) -> Result<Self, Error> {
+        let descriptors = cfs
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()))
+            .collect::<Vec<_>>();
+        Self::open_cf_descriptors(opts, txn_db_opts, path, descriptors)
+    }
+
+    /// Opens the given column families, each with its own `Options`.
+    pub fn open_cf_descriptors<P: AsRef<Path>>(
+        opts: &Options,
+        txn_db_opts: &TransactionDBOptions,
+        path: P,
+        cfs: Vec<ColumnFamilyDescriptor>,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let cpath = utils::to_cpath(path)?;
+        let db: *mut ffi::rocksdb_transactiondb_t;
+        let mut cf_map = BTreeMap::new();
+
+        if cfs.is_empty() {
+            unsafe {
+                db = ffi_try!(ffi::rocksdb_transactiondb_open(
+                    opts.inner,
+                    txn_db_opts.inner,
+                    cpath.as_ptr() as *const _
+                ));
+            }
+            return if db.is_null() {
+                Err(Error::new("Could not initialize database.".to_owned()))
+            } else {
+                Ok(TransactionDB {
+                    inner: db,
+                    cfs: cf_map,
+                    cf_opts: Vec::new(),
+                })
+            };
+        }
+
+        let mut cfs = cfs;
+        // Always open the default column family.
+        if !cfs.iter().any(|d| d.name == "default") {
+            cfs.push(ColumnFamilyDescriptor::new("default", Options::default()));
+        }
+
+        // We need to store our CStrings in an intermediate vector
+        // so that their pointers remain valid.
+        let c_cfs: Vec<CString> = cfs
+            .iter()
+            .map(|d| CString::new(d.name.as_bytes()).unwrap())
+            .collect();
+
+        let cfnames: Vec<_> = c_cfs.iter().map(|cf| cf.as_ptr()).collect();
+
+        // These handles will be populated by DB.
+        let mut cfhandles: Vec<_> = cfs.iter().map(|_| ptr::null_mut()).collect();
+
+        let cfopts: Vec<_> = cfs.iter().map(|d| d.options.inner as *const _).collect();
+
+        unsafe {
+            db = ffi_try!(ffi::rocksdb_transactiondb_open_column_families(
+                opts.inner,
+                txn_db_opts.inner,
+                cpath.as_ptr() as *const _,
+                cfs.len() as c_int,
+                cfnames.as_ptr() as *const _,
+                cfopts.as_ptr(),
+                cfhandles.as_mut_ptr()
+            ));
+        }
+
+        for handle in &cfhandles {
+            if handle.is_null() {
+                return Err(Error::new(
+                    "Received null column family \
+                                       handle from DB."
+                        .to_owned(),
+                ));
+            }
+        }
+
+        for (d, h) in cfs.iter().zip(&cfhandles) {
+            cf_map.insert(d.name.clone(), ColumnFamily { inner: *h });
+        }
+
+        if db.is_null() {
+            return Err(Error::new("Could not initialize database.".to_owned()));
+        }
+
+        let cf_opts = cfs.into_iter().map(|d| d.options).collect();
+
+        Ok(TransactionDB {
+            inner: db,
+            cfs: cf_map,
+            cf_opts,
+        })
+    }
+
+    pub fn transaction_begin<'a>(
+        &'a self,
+        w_opts: &WriteOptions,
+        txn_opts: &TransactionOptions,
+    ) -> Transaction<'a> {
+        Transaction::new_pessimistic(self, w_opts, txn_opts)
+    }
+
+    pub fn snapshot<'a>(&'a self) -> Snapshot<'a> {
+        Snapshot::new_pessimistic(self)
+    }
+
+    pub fn cf_handle(&self, name: &str) -> Option<ColumnFamily> {
+        self.cfs.get(name).cloned()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<DBVector>, Error> {
+        let r_opts = ReadOptions::default();
+        self.get_opt(key, &r_opts)
+    }
+
+    pub fn get_opt(&self, key: &[u8], r_opts: &ReadOptions) -> Result<Option<DBVector>, Error> {
+        unsafe {
+            let mut val_len: size_t = 0;
+            let val = ffi_try!(ffi::rocksdb_transactiondb_get(
+                self.inner,
+                r_opts.inner,
+                key.as_ptr() as *const c_char,
+                key.len() as size_t,
+                &mut val_len
+            )) as *mut u8;
+            if val.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(DBVector::from_c(val, val_len)))
+            }
+        }
+    }
+
+    /// Returns the transactions left dangling in a prepared (but neither committed nor rolled
+    /// back) state, e.g. because the process crashed between `Transaction::prepare()` and
+    /// `commit()`/`rollback()`. A 2PC coordinator can inspect each one's `get_name()` and resolve
+    /// it by calling `commit()` or `rollback()` on the returned handle.
+    pub fn get_prepared_transactions(&self) -> Vec<Transaction> {
+        unsafe {
+            let mut cnt: size_t = 0;
+            let ptrs = ffi::rocksdb_transactiondb_get_prepared_transactions(self.inner, &mut cnt);
+            if ptrs.is_null() {
+                return Vec::new();
+            }
+            let raw = ::std::slice::from_raw_parts(ptrs, cnt as usize);
+            let txns = raw.iter().map(|&inner| Transaction::from_raw(inner)).collect();
+            ffi::rocksdb_free(ptrs as *mut ::libc::c_void);
+            txns
+        }
+    }
+
+    pub fn destroy<P: AsRef<Path>>(opts: &Options, path: P) -> Result<(), Error> {
+        let cpath = utils::to_cpath(path.as_ref())?;
+        unsafe {
+            ffi_try!(ffi::rocksdb_destroy_db(opts.inner, cpath.as_ptr()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TransactionDB {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_transactiondb_close(self.inner);
+        }
+    }
+}
+
+/// Database-wide options for a `TransactionDB`, controlling the default locking behavior of
+/// every transaction it begins.
+pub struct TransactionDBOptions {
+    pub inner: *mut ffi::rocksdb_transactiondb_options_t,
+}
+
+impl TransactionDBOptions {
+    /// Sets how long (in milliseconds) a write made directly on the database, outside any
+    /// transaction, waits on a key locked by a transaction before timing out. `0` means don't
+    /// wait, negative means wait indefinitely. Defaults to 1000. Writes made inside a transaction
+    /// use `set_transaction_lock_timeout` instead.
+    pub fn set_default_lock_timeout(&mut self, default_lock_timeout: i64) {
+        unsafe {
+            ffi::rocksdb_transactiondb_options_set_default_lock_timeout(
+                self.inner,
+                default_lock_timeout,
+            );
+        }
+    }
+
+    /// Sets the number of lock table stripes used to shard the row lock table; more stripes
+    /// reduce false contention between unrelated keys at the cost of memory.
+    pub fn set_num_stripes(&mut self, num_stripes: usize) {
+        unsafe {
+            ffi::rocksdb_transactiondb_options_set_num_stripes(self.inner, num_stripes);
+        }
+    }
+
+    /// Caps the total number of locks the database will hold at once; `0` means unlimited.
+    pub fn set_max_num_locks(&mut self, max_num_locks: i64) {
+        unsafe {
+            ffi::rocksdb_transactiondb_options_set_max_num_locks(self.inner, max_num_locks);
+        }
+    }
+
+    /// Sets how long (in milliseconds) a transaction waits on a lock before timing out, for every
+    /// transaction that does not set its own `TransactionOptions::set_lock_timeout`. `0` means
+    /// don't wait, negative means wait indefinitely. Defaults to 1000.
+    pub fn set_transaction_lock_timeout(&mut self, txn_lock_timeout: i64) {
+        unsafe {
+            ffi::rocksdb_transactiondb_options_set_transaction_lock_timeout(
+                self.inner,
+                txn_lock_timeout,
+            );
+        }
+    }
+}
+
+impl Default for TransactionDBOptions {
+    fn default() -> Self {
+        TransactionDBOptions {
+            inner: unsafe { ffi::rocksdb_transactiondb_options_create() },
+        }
+    }
+}
+
+impl Drop for TransactionDBOptions {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_transactiondb_options_destroy(self.inner);
+        }
+    }
+}
+
+/// Per-transaction options for a transaction begun against a `TransactionDB`.
+pub struct TransactionOptions {
+    pub inner: *mut ffi::rocksdb_transaction_options_t,
+}
+
+impl TransactionOptions {
+    /// Overrides the database's `transaction_lock_timeout` for this transaction only.
+    pub fn set_lock_timeout(&mut self, lock_timeout: i64) {
+        unsafe {
+            ffi::rocksdb_transaction_options_set_lock_timeout(self.inner, lock_timeout);
+        }
+    }
+
+    pub fn set_deadlock_detect(&mut self, deadlock_detect: bool) {
+        unsafe {
+            ffi::rocksdb_transaction_options_set_deadlock_detect(
+                self.inner,
+                deadlock_detect as ::libc::c_uchar,
+            );
+        }
+    }
+
+    /// Instructs the transaction to capture a snapshot of the database when it begins, so it can
+    /// later be retrieved with `rocksdb_transaction_get_snapshot`. Used by `TransactionDB::snapshot`,
+    /// analogous to `OptimisticTransactionOptions::set_snapshot`.
+    pub fn set_snapshot(&mut self, snapshot: bool) {
+        unsafe {
+            ffi::rocksdb_transaction_options_set_set_snapshot(self.inner, snapshot as c_uchar);
+        }
+    }
+}
+
+impl Default for TransactionOptions {
+    fn default() -> Self {
+        TransactionOptions {
+            inner: unsafe { ffi::rocksdb_transaction_options_create() },
+        }
+    }
+}
+
+impl Drop for TransactionOptions {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_transaction_options_destroy(self.inner);
+        }
+    }
+}
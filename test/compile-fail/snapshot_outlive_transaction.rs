@@ -0,0 +1,30 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum_rocksdb;
+extern crate tempdir;
+
+use exonum_rocksdb::{OptimisticTransactionDB, Snapshot};
+use tempdir::TempDir;
+
+fn main() {
+    let snapshot: Snapshot;
+    {
+        let temp_dir = TempDir::new("snapshot_outlive_transaction_db").unwrap();
+        let db = OptimisticTransactionDB::open_default(temp_dir.path()).unwrap();
+        snapshot = db.snapshot();
+        //~^ ERROR `db` does not live long enough
+    }
+    let _ = snapshot.get(b"key");
+}
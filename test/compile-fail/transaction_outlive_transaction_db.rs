@@ -0,0 +1,32 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum_rocksdb;
+extern crate tempdir;
+
+use exonum_rocksdb::{Transaction, TransactionDB, TransactionOptions, WriteOptions};
+use tempdir::TempDir;
+
+fn main() {
+    let txn: Transaction;
+    {
+        let temp_dir = TempDir::new("transaction_outlive_transaction_db").unwrap();
+        let db = TransactionDB::open_default(temp_dir.path()).unwrap();
+        let w_opts = WriteOptions::default();
+        let txn_opts = TransactionOptions::default();
+        txn = db.transaction_begin(&w_opts, &txn_opts);
+        //~^ ERROR `db` does not live long enough
+    }
+    let _ = txn.get(b"key");
+}
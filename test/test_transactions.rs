@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use exonum_rocksdb::{TransactionDB, WriteOptions, TransactionOptions, IteratorMode, Options};
+use exonum_rocksdb::{TransactionDB, TransactionDBOptions, ColumnFamilyDescriptor, WriteOptions,
+                     TransactionOptions, IteratorMode, Options, SliceTransform};
 use tempdir::TempDir;
 
 #[test]
@@ -111,3 +112,150 @@ fn test_transaction_savepoint() {
     assert!(txn.rollback_to_savepoint().is_ok());
     assert!(txn.get(b"key2").unwrap().is_none());
 }
+
+#[test]
+fn test_transactiondb_lock_timeout() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    let mut txn_db_opts = TransactionDBOptions::default();
+    // Don't block waiting on a locked row; fail the conflicting transaction right away.
+    txn_db_opts.set_default_lock_timeout(0);
+    txn_db_opts.set_transaction_lock_timeout(0);
+    txn_db_opts.set_max_num_locks(1000);
+    txn_db_opts.set_num_stripes(4);
+    let db = TransactionDB::open(&opts, &txn_db_opts, path).unwrap();
+    let w_opts = WriteOptions::default();
+    let txn_opts = TransactionOptions::default();
+
+    let txn1 = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn1.put(b"key1", b"value1").is_ok());
+
+    let txn2 = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn2.put(b"key1", b"value2").is_err());
+
+    assert!(txn1.commit().is_ok());
+}
+
+#[test]
+fn test_transaction_get_for_update() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    let mut txn_db_opts = TransactionDBOptions::default();
+    txn_db_opts.set_default_lock_timeout(0);
+    let db = TransactionDB::open(&opts, &txn_db_opts, path).unwrap();
+    let w_opts = WriteOptions::default();
+    let txn_opts = TransactionOptions::default();
+
+    let txn1 = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn1.put(b"key1", b"value1").is_ok());
+    assert!(txn1.commit().is_ok());
+
+    let txn2 = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn2.get_for_update(b"key1", true).unwrap().is_some());
+
+    // txn3 cannot take the exclusive lock txn2 is holding, so its put fails.
+    let txn3 = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn3.put(b"key1", b"value3").is_err());
+
+    assert!(txn2.commit().is_ok());
+}
+
+#[test]
+fn test_transaction_two_phase_commit() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    let txn_db_opts = TransactionDBOptions::default();
+    let w_opts = WriteOptions::default();
+    let txn_opts = TransactionOptions::default();
+
+    {
+        let db = TransactionDB::open(&opts, &txn_db_opts, path).unwrap();
+        let txn = db.transaction_begin(&w_opts, &txn_opts);
+        assert!(txn.set_name("coordinator-txn-1").is_ok());
+        assert_eq!(txn.get_name(), Some("coordinator-txn-1".to_owned()));
+        assert!(txn.put(b"key1", b"value1").is_ok());
+        assert!(txn.prepare().is_ok());
+        // Deliberately neither commit() nor rollback(): the transaction is left prepared,
+        // simulating a crash before the coordinator resolves it.
+    }
+
+    let db = TransactionDB::open(&opts, &txn_db_opts, path).unwrap();
+    assert!(db.get(b"key1").unwrap().is_none());
+    let prepared = db.get_prepared_transactions();
+    assert_eq!(prepared.len(), 1);
+    assert_eq!(prepared[0].get_name(), Some("coordinator-txn-1".to_owned()));
+    assert!(prepared[0].commit().is_ok());
+    assert_eq!(db.get(b"key1").unwrap().unwrap().to_utf8(), Some("value1"));
+}
+
+#[test]
+fn test_transactiondb_open_cf_descriptors() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let txn_db_opts = TransactionDBOptions::default();
+
+    let mut cf1_opts = Options::default();
+    cf1_opts.set_max_write_buffer_number(4);
+    let mut cf2_opts = Options::default();
+    cf2_opts.set_max_write_buffer_number(2);
+
+    let descriptors = vec![
+        ColumnFamilyDescriptor::new("cf1", cf1_opts),
+        ColumnFamilyDescriptor::new("cf2", cf2_opts),
+    ];
+    let db = TransactionDB::open_cf_descriptors(
+        &Options::default(),
+        &txn_db_opts,
+        path,
+        descriptors,
+    ).unwrap();
+
+    assert!(db.cf_handle("cf1").is_some());
+    assert!(db.cf_handle("cf2").is_some());
+}
+
+#[test]
+fn test_transaction_multi_get() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let db = TransactionDB::open_default(path).unwrap();
+    let w_opts = WriteOptions::default();
+    let txn_opts = TransactionOptions::default();
+    let txn = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn.put(b"key1", b"value1").is_ok());
+    assert!(txn.put(b"key2", b"value2").is_ok());
+
+    let results = txn.multi_get(&[b"key1", b"key2", b"key3"]);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().as_ref().unwrap().to_utf8(), Some("value1"));
+    assert_eq!(results[1].as_ref().unwrap().as_ref().unwrap().to_utf8(), Some("value2"));
+    assert!(results[2].as_ref().unwrap().is_none());
+}
+
+#[test]
+fn test_transaction_prefix_iterator() {
+    let temp_dir = TempDir::new("transaction_db").unwrap();
+    let path = temp_dir.path();
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    // Ties `prefix_same_as_start` to the first two bytes of each key, so the iterator is
+    // actually bounded to keys sharing that prefix rather than relying on unspecified behavior.
+    opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(2));
+    let txn_db_opts = TransactionDBOptions::default();
+    let db = TransactionDB::open(&opts, &txn_db_opts, path).unwrap();
+    let w_opts = WriteOptions::default();
+    let txn_opts = TransactionOptions::default();
+    let txn = db.transaction_begin(&w_opts, &txn_opts);
+    assert!(txn.put(b"aaa", b"1").is_ok());
+    assert!(txn.put(b"aab", b"2").is_ok());
+    assert!(txn.put(b"zzz", b"3").is_ok());
+
+    let iter = txn.prefix_iterator(b"aa");
+    assert_eq!(iter.count(), 2);
+}